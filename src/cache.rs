@@ -1,5 +1,15 @@
 use std::collections::HashMap;
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::future::Future;
+use std::hash::{BuildHasher, Hash, Hasher};
 use std::mem::{swap, replace, transmute_copy};
+use std::pin::Pin;
+use std::result;
+use std::sync::{Arc, Mutex};
+
+use futures::future::{Shared, FutureExt};
+
+use crate::error::{Result, Status};
 
 // No clone, no copy! That asserts that an LRUHandle exists only once.
 type LRUHandle<T> = *mut LRUNode<T>;
@@ -88,6 +98,12 @@ impl<T> LRUList<T> {
             // If has next
             if let Some(ref mut nextp) = (*node_handle).next {
                 swap(&mut (**nextp).prev, &mut (*node_handle).prev);
+            } else {
+                // node_handle is the tail; the list's tail pointer must move to its
+                // predecessor, or become empty if node_handle was the only node.
+                let prevp = (*node_handle).prev.unwrap();
+                let head_ptr: *mut LRUNode<T> = &mut self.head;
+                self.head.prev = if prevp == head_ptr { None } else { Some(prevp) };
             }
             // If has prev
             if let Some(ref mut prevp) = (*node_handle).prev {
@@ -136,6 +152,33 @@ impl<T> LRUList<T> {
         }
     }
 
+    /// Reinserts the referenced node at the back (the least-recently-used end, i.e. the next
+    /// one `remove_last()` would return). Mirrors `reinsert_front` in the opposite direction.
+    fn reinsert_back(&mut self, node_handle: LRUHandle<T>) {
+        unsafe {
+            // Already the last node; nothing to do.
+            if (*node_handle).next.is_none() {
+                return;
+            }
+
+            let prevp = (*node_handle).prev.unwrap();
+            let old_tail = self.head.prev.unwrap();
+
+            // Unlink node_handle from its current position.
+            (*node_handle).next.as_mut().unwrap().prev = Some(prevp);
+            swap(&mut (*prevp).next, &mut (*node_handle).next);
+            // node_handle.next now refers to itself (see reinsert_front for why).
+
+            // Splice node_handle onto the end, after the current last node.
+            swap(&mut (*old_tail).next, &mut (*node_handle).next);
+            (*node_handle).prev = Some(old_tail);
+            self.head.prev = Some(node_handle);
+
+            assert!(self.head.next.is_some());
+            assert!(self.head.prev.is_some());
+        }
+    }
+
     fn count(&self) -> usize {
         self.count
     }
@@ -150,26 +193,40 @@ impl<T> LRUList<T> {
 }
 
 pub type CacheKey = Vec<u8>;
-type CacheEntry<T> = (T, LRUHandle<CacheKey>);
+type CacheEntry<T> = (T, LRUHandle<Arc<CacheKey>>, usize);
 
-/// Implementation of `ShardedLRUCache`.
+/// A single, unsharded LRU cache.
 /// Based on a HashMap; the elements are linked in order to support the LRU ordering.
-pub struct Cache<T> {
-    // note: CacheKeys (Vec<u8>) are duplicated between list and map. If this turns out to be a
-    // performance bottleneck, another layer of indirection™ can solve this by mapping the key
-    // to a numeric handle that keys both list and map.
-    list: LRUList<CacheKey>,
-    map: HashMap<CacheKey, CacheEntry<T>>,
+///
+/// This is the building block for `ShardedLRUCache`, which spreads entries across several of
+/// these behind independent locks to reduce contention.
+///
+/// `S` is the `HashMap` hasher; it defaults to `RandomState` (DoS-resistant) but callers that
+/// only ever use trusted, internal keys (e.g. block handles) can plug in a faster hasher.
+pub struct Cache<T, S = RandomState> {
+    // Each key is stored exactly once, in an `Arc<CacheKey>`; `list` and `map` each hold a
+    // cheap refcounted clone of that single allocation rather than their own copy of the bytes.
+    list: LRUList<Arc<CacheKey>>,
+    map: HashMap<Arc<CacheKey>, CacheEntry<T>, S>,
     cap: usize,
+    usage: usize,
 }
 
-impl<T> Cache<T> {
-    pub fn new(capacity: usize) -> Cache<T> {
+impl<T> Cache<T, RandomState> {
+    pub fn new(capacity: usize) -> Cache<T, RandomState> {
+        Cache::with_hasher(capacity)
+    }
+}
+
+impl<T, S: BuildHasher + Default> Cache<T, S> {
+    /// Like `new()`, but with an explicit `HashMap` hasher.
+    pub fn with_hasher(capacity: usize) -> Cache<T, S> {
         assert!(capacity > 0);
         Cache {
             list: LRUList::new(),
-            map: HashMap::with_capacity(1024),
+            map: HashMap::with_capacity_and_hasher(1024, S::default()),
             cap: capacity,
+            usage: 0,
         }
     }
 
@@ -178,26 +235,54 @@ impl<T> Cache<T> {
         return self.list.count();
     }
 
-    /// The capacity of this cache
+    /// The capacity of this cache, in the same unit as the `charge` passed to `insert` (usually
+    /// bytes).
     pub fn cap(&self) -> usize {
         return self.cap;
     }
 
-    /// Insert a new element into the cache. The returned `CacheHandle` can be used for further
-    /// operations on that element.
-    /// If the capacity has been reached, the least recently used element is removed from the
-    /// cache.
-    pub fn insert(&mut self, key: &CacheKey, elem: T) {
-        if self.list.count() >= self.cap {
+    /// Changes the capacity of this cache. If the new capacity is smaller than the current
+    /// usage, least-recently-used entries are evicted until usage fits within it again.
+    pub fn set_capacity(&mut self, cap: usize) {
+        assert!(cap > 0);
+        self.cap = cap;
+        self.evict_to_capacity();
+    }
+
+    /// The sum of the charges of all entries currently in the cache.
+    pub fn total_charge(&self) -> usize {
+        return self.usage;
+    }
+
+    /// Evicts least-recently-used entries until `usage` is within `cap`.
+    fn evict_to_capacity(&mut self) {
+        while self.usage > self.cap {
             if let Some(removed_key) = self.list.remove_last() {
-                assert!(self.map.remove(&removed_key).is_some());
+                let (_, _, charge) = self.map
+                    .remove(&removed_key)
+                    .expect("cache map/list out of sync; bug!");
+                self.usage -= charge;
             } else {
                 panic!("could not remove_last(); bug!");
             }
         }
+    }
+
+    /// Insert a new element into the cache with the given charge (its weight against `cap`,
+    /// usually its size in bytes). If the capacity has been exceeded, least recently used
+    /// elements are evicted until usage fits within capacity again.
+    ///
+    /// If `key` is already present, the old entry is dropped first, so it isn't left behind as
+    /// an orphaned, never-reachable list node.
+    pub fn insert(&mut self, key: &CacheKey, elem: T, charge: usize) {
+        self.remove(key);
 
+        let key = Arc::new(key.clone());
         let lru_handle = self.list.insert(key.clone());
-        self.map.insert(key.clone(), (elem, lru_handle));
+        self.map.insert(key, (elem, lru_handle, charge));
+        self.usage += charge;
+
+        self.evict_to_capacity();
     }
 
     /// Retrieve an element from the cache.
@@ -205,7 +290,7 @@ impl<T> Cache<T> {
     pub fn get<'a>(&'a mut self, key: &CacheKey) -> Option<&'a T> {
         match self.map.get(key) {
             None => None,
-            Some(&(ref elem, ref lru_handle)) => {
+            Some(&(ref elem, ref lru_handle, _)) => {
                 self.list.reinsert_front(*lru_handle);
                 Some(elem)
             }
@@ -216,12 +301,192 @@ impl<T> Cache<T> {
     pub fn remove(&mut self, key: &CacheKey) -> Option<T> {
         match self.map.remove(key) {
             None => None,
-            Some((elem, lru_handle)) => {
+            Some((elem, lru_handle, charge)) => {
                 self.list.remove(lru_handle);
+                self.usage -= charge;
                 Some(elem)
             }
         }
     }
+
+    /// Look up an element without perturbing its LRU position. Unlike `get`, this does not
+    /// require `&mut self`, since it never touches the `LRUList`.
+    pub fn peek(&self, key: &CacheKey) -> Option<&T> {
+        self.map.get(key).map(|&(ref elem, _, _)| elem)
+    }
+
+    /// Marks an entry as most-recently-used, without retrieving it. A no-op if `key` is not
+    /// present.
+    pub fn promote(&mut self, key: &CacheKey) {
+        if let Some(&(_, lru_handle, _)) = self.map.get(key) {
+            self.list.reinsert_front(lru_handle);
+        }
+    }
+
+    /// Marks an entry as least-recently-used, making it the next victim of eviction. A no-op
+    /// if `key` is not present.
+    pub fn demote(&mut self, key: &CacheKey) {
+        if let Some(&(_, lru_handle, _)) = self.map.get(key) {
+            self.list.reinsert_back(lru_handle);
+        }
+    }
+}
+
+// SAFETY: the only non-`Send` part of `Cache` is the raw `*mut LRUNode` pointers inside `list`.
+// Those pointers never escape `Cache` and are only ever dereferenced from `&mut self` methods;
+// every caller that moves a `Cache` across threads (`ShardedLRUCache`, `AsyncCache`) holds it
+// behind a `Mutex`, which serializes all such access. So moving a whole `Cache` to another
+// thread, or sharing `&Cache` via a `Mutex`, is sound.
+unsafe impl<T: Send, S: Send> Send for Cache<T, S> {}
+
+/// Default number of shards for `ShardedLRUCache`. Must be a power of two; upstream LevelDB
+/// uses the same default for its block cache.
+const DEFAULT_NUM_SHARDS: usize = 16;
+
+fn shard_ix(key: &CacheKey, num_shards: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() as usize) & (num_shards - 1)
+}
+
+/// A cache made up of several independent `Cache<T, S>` shards, each behind its own `Mutex`.
+/// Each key is routed to exactly one shard by hashing, so readers touching different shards
+/// never contend on the same lock. This is how upstream LevelDB's block and table caches are
+/// built, and is what the unsharded `Cache<T>` above was previously (incorrectly) documented
+/// to be.
+///
+/// `S` is threaded through to each shard's `Cache`, so a `ShardedLRUCache` for trusted internal
+/// keys can use a faster, non-DoS-resistant hasher just like a bare `Cache` can.
+pub struct ShardedLRUCache<T, S = RandomState> {
+    shards: Vec<Mutex<Cache<T, S>>>,
+    num_shards: usize,
+}
+
+impl<T> ShardedLRUCache<T, RandomState> {
+    /// Creates a cache with the default number of shards (16) and the given total capacity,
+    /// split evenly between shards.
+    pub fn new(total_capacity: usize) -> ShardedLRUCache<T, RandomState> {
+        Self::with_shards(total_capacity, DEFAULT_NUM_SHARDS)
+    }
+
+    /// Like `new()`, but with an explicit shard count. `num_shards` must be a power of two.
+    pub fn with_shards(total_capacity: usize, num_shards: usize) -> ShardedLRUCache<T, RandomState> {
+        Self::with_shards_and_hasher(total_capacity, num_shards)
+    }
+}
+
+impl<T, S: BuildHasher + Default> ShardedLRUCache<T, S> {
+    /// Like `with_shards()`, but with an explicit per-shard `Cache` hasher.
+    pub fn with_shards_and_hasher(total_capacity: usize, num_shards: usize) -> ShardedLRUCache<T, S> {
+        assert!(num_shards > 0);
+        assert!(num_shards.is_power_of_two());
+
+        let shard_cap = std::cmp::max(1, total_capacity / num_shards);
+        ShardedLRUCache {
+            shards: (0..num_shards)
+                .map(|_| Mutex::new(Cache::with_hasher(shard_cap)))
+                .collect(),
+            num_shards,
+        }
+    }
+
+    fn shard(&self, key: &CacheKey) -> &Mutex<Cache<T, S>> {
+        &self.shards[shard_ix(key, self.num_shards)]
+    }
+
+    /// Insert a new element into the cache, evicting from the owning shard if necessary.
+    pub fn insert(&self, key: &CacheKey, elem: T, charge: usize) {
+        self.shard(key).lock().unwrap().insert(key, elem, charge);
+    }
+
+    /// Remove an element from the cache (for invalidation).
+    pub fn remove(&self, key: &CacheKey) -> Option<T> {
+        self.shard(key).lock().unwrap().remove(key)
+    }
+
+    /// The number of entries currently held across all shards.
+    pub fn count(&self) -> usize {
+        self.shards.iter().map(|s| s.lock().unwrap().count()).sum()
+    }
+}
+
+impl<T: Clone, S: BuildHasher + Default> ShardedLRUCache<T, S> {
+    /// Retrieve a clone of an element from the cache. Returns a clone (rather than a reference)
+    /// because the entry lives behind a per-shard lock that is released before returning.
+    pub fn get(&self, key: &CacheKey) -> Option<T> {
+        self.shard(key).lock().unwrap().get(key).cloned()
+    }
+}
+
+// `futures::future::Shared` requires its output to be `Clone` so every waiter can receive its
+// own copy; `Status` isn't `Clone`, so a fetch error is shared as an `Arc<Status>` instead of a
+// bare `Status`.
+type FetchResult<T> = result::Result<T, Arc<Status>>;
+type FetchFuture<T> = Pin<Box<dyn Future<Output = FetchResult<T>> + Send>>;
+
+/// Wraps a `Cache<T, S>` so that concurrent misses on the same key are single-flighted: if a
+/// fetch for a key is already in progress, later callers await that fetch instead of starting a
+/// redundant one. Useful for an async DB front-end, where many tasks can miss on the same
+/// SSTable block at once.
+///
+/// `S` is threaded through to the inner `Cache` for the same reason as on `ShardedLRUCache`.
+pub struct AsyncCache<T: Clone + Send + 'static, S = RandomState> {
+    inner: Mutex<Cache<T, S>>,
+    in_flight: Mutex<HashMap<CacheKey, Shared<FetchFuture<T>>>>,
+}
+
+impl<T: Clone + Send + 'static> AsyncCache<T, RandomState> {
+    pub fn new(capacity: usize) -> AsyncCache<T, RandomState> {
+        Self::with_hasher(capacity)
+    }
+}
+
+impl<T: Clone + Send + 'static, S: BuildHasher + Default + Send + 'static> AsyncCache<T, S> {
+    /// Like `new()`, but with an explicit `Cache` hasher.
+    pub fn with_hasher(capacity: usize) -> AsyncCache<T, S> {
+        AsyncCache {
+            inner: Mutex::new(Cache::with_hasher(capacity)),
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the cached value for `key`, fetching it via `fetch` on a miss. If another caller
+    /// is already fetching the same key, this awaits that in-flight fetch instead of running
+    /// `fetch` again; every waiter for a key receives the same result once the one fetch
+    /// resolves. The fetch error is wrapped in an `Arc` since it may be handed out to several
+    /// waiters at once; see `FetchResult`.
+    pub async fn get_or_fetch<F>(&self, key: &CacheKey, charge: usize, fetch: F) -> FetchResult<T>
+        where F: Future<Output = Result<T>> + Send + 'static
+    {
+        if let Some(elem) = self.inner.lock().unwrap().get(key) {
+            return Ok(elem.clone());
+        }
+
+        let shared = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(key) {
+                Some(existing) => existing.clone(),
+                None => {
+                    let boxed: FetchFuture<T> = Box::pin(async move { fetch.await.map_err(Arc::new) });
+                    let shared = boxed.shared();
+                    in_flight.insert(key.clone(), shared.clone());
+                    shared
+                }
+            }
+        };
+
+        let result = shared.await;
+
+        // The fetch has resolved for every waiter; drop the in-flight entry so the next miss
+        // starts a fresh fetch instead of replaying this (by-then-stale) result forever.
+        self.in_flight.lock().unwrap().remove(key);
+
+        if let Ok(ref elem) = result {
+            self.inner.lock().unwrap().insert(key, elem.clone(), charge);
+        }
+
+        result
+    }
 }
 
 #[cfg(test)]
@@ -229,6 +494,36 @@ mod tests {
     use super::*;
     use super::LRUList;
 
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+    fn assert_send_val<T: Send>(_: &T) {}
+
+    #[test]
+    fn test_blockcache_sharded_cache_is_send_sync() {
+        // `Arc<ShardedLRUCache<T>>` must be shareable across threads, or sharding into
+        // independent per-shard locks buys nothing; this fails to compile otherwise.
+        assert_send::<ShardedLRUCache<Vec<u8>>>();
+        assert_sync::<ShardedLRUCache<Vec<u8>>>();
+    }
+
+    #[test]
+    fn test_blockcache_async_cache_is_send_sync() {
+        // `AsyncCache` must be `Send`/`Sync` for `&AsyncCache` to be usable from a spawned task.
+        assert_send::<AsyncCache<i32>>();
+        assert_sync::<AsyncCache<i32>>();
+    }
+
+    #[test]
+    fn test_blockcache_async_cache_future_is_send() {
+        // `get_or_fetch`'s future must be `Send` to be spawned onto a multithreaded executor
+        // (e.g. `tokio::spawn`); this only compiles because `Cache`, and thus `AsyncCache`, is
+        // `Send`.
+        let cache = AsyncCache::<i32>::new(8);
+        let key = "aaa".as_bytes().to_vec();
+        let fut = cache.get_or_fetch(&key, 1, async { Ok(1) });
+        assert_send_val(&fut);
+    }
+
     #[test]
     fn test_blockcache_cache_add_rm() {
         let mut cache = Cache::new(128);
@@ -239,11 +534,11 @@ mod tests {
         let h_332 = "aad".as_bytes().to_vec();
         let h_899 = "aae".as_bytes().to_vec();
 
-        cache.insert(&h_123, 123);
-        cache.insert(&h_332, 332);
-        cache.insert(&h_521, 521);
-        cache.insert(&h_372, 372);
-        cache.insert(&h_899, 899);
+        cache.insert(&h_123, 123, 1);
+        cache.insert(&h_332, 332, 1);
+        cache.insert(&h_521, 521, 1);
+        cache.insert(&h_372, 372, 1);
+        cache.insert(&h_899, 899, 1);
 
         assert_eq!(cache.count(), 5);
 
@@ -267,11 +562,11 @@ mod tests {
         let h_332 = "aad".as_bytes().to_vec();
         let h_899 = "aae".as_bytes().to_vec();
 
-        cache.insert(&h_123, 123);
-        cache.insert(&h_332, 332);
-        cache.insert(&h_521, 521);
-        cache.insert(&h_372, 372);
-        cache.insert(&h_899, 899);
+        cache.insert(&h_123, 123, 1);
+        cache.insert(&h_332, 332, 1);
+        cache.insert(&h_521, 521, 1);
+        cache.insert(&h_372, 372, 1);
+        cache.insert(&h_899, 899, 1);
 
         assert_eq!(cache.count(), 3);
 
@@ -282,6 +577,180 @@ mod tests {
         assert_eq!(cache.get(&h_899), Some(&899));
     }
 
+    #[test]
+    fn test_blockcache_cache_charge() {
+        let mut cache = Cache::new(10);
+
+        let h_a = "aaa".as_bytes().to_vec();
+        let h_b = "aab".as_bytes().to_vec();
+        let h_c = "aac".as_bytes().to_vec();
+
+        cache.insert(&h_a, "a", 6);
+        cache.insert(&h_b, "b", 3);
+        assert_eq!(cache.total_charge(), 9);
+        assert_eq!(cache.count(), 2);
+
+        // Pushes total charge to 15, over the cap of 10; the LRU entry (h_a) is evicted to
+        // bring usage back within capacity.
+        cache.insert(&h_c, "c", 6);
+
+        assert_eq!(cache.get(&h_a), None);
+        assert_eq!(cache.get(&h_b), Some(&"b"));
+        assert_eq!(cache.get(&h_c), Some(&"c"));
+        assert_eq!(cache.total_charge(), 9);
+    }
+
+    #[test]
+    fn test_blockcache_cache_reinsert_same_key() {
+        let mut cache = Cache::new(10);
+
+        let h_a = "aaa".as_bytes().to_vec();
+        let h_b = "aab".as_bytes().to_vec();
+
+        cache.insert(&h_a, 1, 2);
+        cache.insert(&h_b, 2, 2);
+        assert_eq!(cache.total_charge(), 4);
+
+        // Re-inserting h_a should replace it (not leave the old entry behind to confuse
+        // usage accounting or eviction order).
+        cache.insert(&h_a, 10, 3);
+
+        assert_eq!(cache.count(), 2);
+        assert_eq!(cache.total_charge(), 5);
+        assert_eq!(cache.get(&h_a), Some(&10));
+        assert_eq!(cache.get(&h_b), Some(&2));
+    }
+
+    #[test]
+    fn test_blockcache_cache_custom_hasher() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::BuildHasherDefault;
+
+        // A plugged-in hasher should behave identically to the default one; this exercises
+        // `Cache<T, S>` with something other than the default `RandomState`.
+        let mut cache: Cache<i32, BuildHasherDefault<DefaultHasher>> = Cache::with_hasher(2);
+
+        let h_a = "aaa".as_bytes().to_vec();
+        let h_b = "aab".as_bytes().to_vec();
+        let h_c = "aac".as_bytes().to_vec();
+
+        cache.insert(&h_a, 1, 1);
+        cache.insert(&h_b, 2, 1);
+        cache.insert(&h_c, 3, 1);
+
+        assert_eq!(cache.count(), 2);
+        assert_eq!(cache.get(&h_a), None);
+        assert_eq!(cache.get(&h_b), Some(&2));
+        assert_eq!(cache.get(&h_c), Some(&3));
+    }
+
+    #[test]
+    fn test_blockcache_cache_set_capacity() {
+        let mut cache = Cache::new(4);
+
+        let h_a = "aaa".as_bytes().to_vec();
+        let h_b = "aab".as_bytes().to_vec();
+        let h_c = "aac".as_bytes().to_vec();
+
+        cache.insert(&h_a, 1, 1);
+        cache.insert(&h_b, 2, 1);
+        cache.insert(&h_c, 3, 1);
+        assert_eq!(cache.count(), 3);
+
+        // Shrinking below current usage evicts least-recently-used entries immediately.
+        cache.set_capacity(2);
+        assert_eq!(cache.cap(), 2);
+        assert_eq!(cache.count(), 2);
+        assert_eq!(cache.get(&h_a), None);
+        assert_eq!(cache.get(&h_b), Some(&2));
+        assert_eq!(cache.get(&h_c), Some(&3));
+
+        // Growing doesn't evict anything.
+        cache.set_capacity(8);
+        assert_eq!(cache.count(), 2);
+    }
+
+    #[test]
+    fn test_blockcache_cache_peek() {
+        let mut cache = Cache::new(2);
+
+        let h_a = "aaa".as_bytes().to_vec();
+        let h_b = "aab".as_bytes().to_vec();
+
+        cache.insert(&h_a, 1, 1);
+        cache.insert(&h_b, 2, 1);
+
+        // peek() doesn't perturb LRU order: h_a is still the least recently used, so a
+        // subsequent insert evicts it, exactly as if it had never been looked up.
+        assert_eq!(cache.peek(&h_a), Some(&1));
+        cache.insert("aac".as_bytes().to_vec().as_ref(), 3, 1);
+        assert_eq!(cache.get(&h_a), None);
+        assert_eq!(cache.get(&h_b), Some(&2));
+    }
+
+    #[test]
+    fn test_blockcache_cache_demote() {
+        let mut cache = Cache::new(2);
+
+        let h_a = "aaa".as_bytes().to_vec();
+        let h_b = "aab".as_bytes().to_vec();
+
+        cache.insert(&h_a, 1, 1);
+        cache.insert(&h_b, 2, 1);
+
+        // h_b is the most-recently-used entry, so it would normally survive the next eviction.
+        // Demoting it makes it the next victim instead of h_a.
+        cache.demote(&h_b);
+        cache.insert("aac".as_bytes().to_vec().as_ref(), 3, 1);
+
+        assert_eq!(cache.get(&h_b), None);
+        assert_eq!(cache.get(&h_a), Some(&1));
+    }
+
+    #[test]
+    fn test_blockcache_cache_promote() {
+        let mut cache = Cache::new(2);
+
+        let h_a = "aaa".as_bytes().to_vec();
+        let h_b = "aab".as_bytes().to_vec();
+
+        cache.insert(&h_a, 1, 1);
+        cache.insert(&h_b, 2, 1);
+
+        // h_a is the least-recently-used entry, so it would normally be evicted next.
+        // Promoting it protects it; h_b is evicted instead.
+        cache.promote(&h_a);
+        cache.insert("aac".as_bytes().to_vec().as_ref(), 3, 1);
+
+        assert_eq!(cache.get(&h_b), None);
+        assert_eq!(cache.get(&h_a), Some(&1));
+    }
+
+    #[test]
+    fn test_blockcache_lru_reinsert_back() {
+        let mut lru = LRUList::<usize>::new();
+
+        let handle1 = lru.insert(56);
+        let handle2 = lru.insert(22);
+        let handle3 = lru.insert(244);
+
+        // Front is most-recently-inserted: 244, 22, 56.
+        assert_eq!(lru._testing_head_ref().map(|r| *r).unwrap(), 244);
+
+        // Demoting the front node makes it the new tail; next-front becomes 22.
+        lru.reinsert_back(handle3);
+        assert_eq!(lru._testing_head_ref().map(|r| *r).unwrap(), 22);
+        assert_eq!(lru.remove_last(), Some(244));
+
+        // Demoting the node that is already the tail (handle1) is a no-op.
+        lru.reinsert_back(handle1);
+        assert_eq!(lru._testing_head_ref().map(|r| *r).unwrap(), 22);
+        assert_eq!(lru.remove_last(), Some(56));
+        assert_eq!(lru.remove_last(), Some(22));
+
+        let _ = handle2;
+    }
+
     #[test]
     fn test_blockcache_lru_remove() {
         let mut lru = LRUList::<usize>::new();
@@ -387,4 +856,118 @@ mod tests {
         assert_eq!(lru.remove_last(), None);
         assert_eq!(lru.remove_last(), None);
     }
+
+    #[test]
+    fn test_blockcache_lru_remove_only_node() {
+        // Removing the sole node via `remove()` (as opposed to `remove_last()`) must also clear
+        // the list's tail pointer, or a later `remove_last()` call would dereference stale state.
+        let mut lru = LRUList::<usize>::new();
+
+        let handle = lru.insert(7);
+        assert_eq!(lru.remove(handle), 7);
+        assert_eq!(lru.remove_last(), None);
+    }
+
+    #[test]
+    fn test_blockcache_sharded_add_rm() {
+        let cache = ShardedLRUCache::new(128);
+
+        let h_123 = "aaa".as_bytes().to_vec();
+        let h_521 = "aab".as_bytes().to_vec();
+        let h_372 = "aac".as_bytes().to_vec();
+
+        cache.insert(&h_123, 123, 1);
+        cache.insert(&h_521, 521, 1);
+        cache.insert(&h_372, 372, 1);
+
+        assert_eq!(cache.count(), 3);
+        assert_eq!(cache.get(&h_123), Some(123));
+        assert_eq!(cache.remove(&h_521), Some(521));
+        assert_eq!(cache.get(&h_521), None);
+        assert_eq!(cache.count(), 2);
+    }
+
+    #[test]
+    fn test_blockcache_sharded_routes_by_hash() {
+        // Entries that hash to different shards should be independently trackable; inserting
+        // many keys and reading them all back exercises every shard at least once.
+        let cache = ShardedLRUCache::with_shards(256, 4);
+
+        for i in 0..64u32 {
+            let key = i.to_be_bytes().to_vec();
+            cache.insert(&key, i, 1);
+        }
+
+        for i in 0..64u32 {
+            let key = i.to_be_bytes().to_vec();
+            assert_eq!(cache.get(&key), Some(i));
+        }
+
+        assert_eq!(cache.count(), 64);
+    }
+
+    #[test]
+    fn test_blockcache_sharded_custom_hasher() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::BuildHasherDefault;
+
+        // `with_shards_and_hasher` should work identically with an explicit hasher, proving the
+        // faster-hasher option threaded through `ShardedLRUCache` is actually reachable.
+        let cache: ShardedLRUCache<i32, BuildHasherDefault<DefaultHasher>> =
+            ShardedLRUCache::with_shards_and_hasher(128, 4);
+
+        let h_a = "aaa".as_bytes().to_vec();
+        let h_b = "aab".as_bytes().to_vec();
+
+        cache.insert(&h_a, 1, 1);
+        cache.insert(&h_b, 2, 1);
+
+        assert_eq!(cache.get(&h_a), Some(1));
+        assert_eq!(cache.remove(&h_b), Some(2));
+        assert_eq!(cache.count(), 1);
+    }
+
+    #[test]
+    fn test_blockcache_async_single_flight() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let cache = AsyncCache::new(8);
+        let key = "aaa".as_bytes().to_vec();
+        let fetch_count = Arc::new(AtomicUsize::new(0));
+
+        // Each fetch yields once before completing, so the first call is still in flight (not
+        // yet cached) when the second call looks the key up.
+        let fetch = |fetch_count: Arc<AtomicUsize>| async move {
+            let mut yielded = false;
+            futures::future::poll_fn(move |cx| {
+                if yielded {
+                    std::task::Poll::Ready(())
+                } else {
+                    yielded = true;
+                    cx.waker().wake_by_ref();
+                    std::task::Poll::Pending
+                }
+            }).await;
+            fetch_count.fetch_add(1, Ordering::SeqCst);
+            Ok(42)
+        };
+
+        // Two concurrent misses on the same key should share one fetch.
+        let (a, b) = futures::executor::block_on(futures::future::join(
+            cache.get_or_fetch(&key, 1, fetch(fetch_count.clone())),
+            cache.get_or_fetch(&key, 1, fetch(fetch_count.clone())),
+        ));
+
+        assert_eq!(a.unwrap(), 42);
+        assert_eq!(b.unwrap(), 42);
+        assert_eq!(fetch_count.load(Ordering::SeqCst), 1);
+
+        // A later call hits the now-populated cache and doesn't fetch again.
+        let c = futures::executor::block_on(
+            cache.get_or_fetch(&key, 1, fetch(fetch_count.clone())),
+        );
+        assert_eq!(c.unwrap(), 42);
+        assert_eq!(fetch_count.load(Ordering::SeqCst), 1);
+    }
 }